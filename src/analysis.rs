@@ -1,26 +1,120 @@
+use crate::cache::{Cache, CacheEntry};
 use chrono::{DateTime, Local, Utc};
 use color_eyre::Result;
 use colored::*;
-use reqwest::{header::HeaderMap, Client};
+use futures::stream::{self, StreamExt};
+use reqwest::{header::HeaderMap, Client, StatusCode};
 use scraper::{Html, Selector};
-use std::collections::HashSet;
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
 use url::Url;
 
-#[derive(Debug)]
+/// Default number of concurrent resource fetches when none is given on the CLI.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The kind of HTML-referenced resource an `AnalysisResult` was found in,
+/// used both to label the result and to group sections in `report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceKind {
+    Image,
+    Script,
+    Stylesheet,
+    Media,
+    Favicon,
+    Frame,
+}
+
+impl ResourceKind {
+    fn label(&self) -> &'static str {
+        match self {
+            ResourceKind::Image => "image",
+            ResourceKind::Script => "script",
+            ResourceKind::Stylesheet => "stylesheet",
+            ResourceKind::Media => "media",
+            ResourceKind::Favicon => "favicon",
+            ResourceKind::Frame => "frame",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct AnalysisResult {
     pub timestamp: DateTime<Utc>,
     pub absolute: String,
     pub internal: bool,
+    pub kind: ResourceKind,
 }
 
+#[derive(Serialize)]
 pub struct Analysis {
     pub url: String,
     pub author: Option<String>,
-    pub images: Vec<AnalysisResult>,
+    pub resources: Vec<AnalysisResult>,
+    #[serde(serialize_with = "serialize_headers")]
     pub headers: HeaderMap,
     pub start: DateTime<Utc>,
     pub end: DateTime<Utc>,
     pub title: Option<String>,
+    pub declared_published: Option<DateTime<Utc>>,
+    pub declared_modified: Option<DateTime<Utc>>,
+}
+
+/// Serializes a `HeaderMap` as a plain `{name: value}` string map, since
+/// `HeaderMap` itself has no `Serialize` impl.
+fn serialize_headers<S>(headers: &HeaderMap, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let map: HashMap<&str, &str> = headers
+        .iter()
+        .filter_map(|(name, value)| value.to_str().ok().map(|value| (name.as_str(), value)))
+        .collect();
+    map.serialize(serializer)
+}
+
+#[derive(Serialize)]
+struct JsonFeed {
+    version: String,
+    title: String,
+    home_page_url: String,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    date_published: String,
+    tags: Vec<String>,
+}
+
+/// Bundles the per-run fetch configuration (HTTP client, concurrency cap, and
+/// cache/refresh policy) so functions that fetch resources take one argument
+/// instead of growing a positional parameter for every new knob.
+pub struct FetchContext<'a> {
+    pub client: &'a Client,
+    pub concurrency: usize,
+    pub cache: Option<&'a Cache>,
+    pub refresh: bool,
+}
+
+impl<'a> FetchContext<'a> {
+    /// `concurrency` is clamped to at least 1: `buffer_unordered(0)` never
+    /// polls any item, so a raw user-supplied `0` would hang forever.
+    pub fn new(
+        client: &'a Client,
+        concurrency: usize,
+        cache: Option<&'a Cache>,
+        refresh: bool,
+    ) -> Self {
+        FetchContext {
+            client,
+            concurrency: concurrency.max(1),
+            cache,
+            refresh,
+        }
+    }
 }
 
 impl Analysis {
@@ -31,25 +125,28 @@ impl Analysis {
         headers: HeaderMap,
         start: DateTime<Utc>,
         end: DateTime<Utc>,
-        client: &Client,
+        ctx: &FetchContext<'_>,
     ) -> Self {
         let title = extract_title(html);
-        let images = collect_images(html, &url, client).await;
+        let metadata = extract_metadata(html);
+        let resources = collect_resources(html, &url, ctx).await;
 
         Analysis {
             url,
-            author,
-            images,
+            author: author.or(metadata.author),
+            resources,
             headers,
             start,
             end,
             title,
+            declared_published: metadata.published,
+            declared_modified: metadata.modified,
         }
     }
 
     fn report_section(&self, title: &str, selector: impl Fn(&AnalysisResult) -> bool) {
         println!("\n{}# {}\n", "#".red(), title);
-        let filtered: Vec<_> = self.images.iter().filter(|i| selector(i)).collect();
+        let filtered: Vec<_> = self.resources.iter().filter(|i| selector(i)).collect();
         if filtered.is_empty() {
             println!("Nothing found.");
             return;
@@ -126,20 +223,175 @@ impl Analysis {
             println!("    {:?}: {}", key, value.to_str().unwrap_or(""));
         }
 
-        self.report_section("Internal images", |r| r.internal);
-        self.report_section("External images", |r| !r.internal);
-        self.report_section("All images", |_| true);
+        println!("\n{}# Declared vs. observed dates\n", "#".red());
+        if let Some(modified) = self.declared_modified {
+            println!(
+                "- {}**Declared modified date:** {}",
+                "Declared modified date".cyan().bold(),
+                modified.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+        let earliest_observed = self.resources.first();
+        match self.declared_published {
+            Some(declared) => {
+                println!(
+                    "- {}**Declared publish date:** {}",
+                    "Declared publish date".cyan().bold(),
+                    declared.format("%Y-%m-%d %H:%M:%S")
+                );
+                match earliest_observed {
+                    Some(earliest) => {
+                        println!(
+                            "- {}**Earliest observed resource:** {} (<{}>)",
+                            "Earliest observed resource".cyan().bold(),
+                            earliest.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                            earliest.absolute
+                        );
+                        if declared > earliest.timestamp {
+                            println!(
+                                "{}",
+                                "WARNING: the declared publish date is later than the earliest \
+                                 observed resource timestamp — a classic indicator of backdated \
+                                 or re-published content."
+                                    .red()
+                                    .bold()
+                            );
+                        }
+                    }
+                    None => println!("No resources were observed to compare against."),
+                }
+            }
+            None => println!("No declared publish date found on the page."),
+        }
+
+        self.report_section("Internal resources", |r| r.internal);
+        self.report_section("External resources", |r| !r.internal);
+
+        self.report_section("Images", |r| r.kind == ResourceKind::Image);
+        self.report_section("Scripts", |r| r.kind == ResourceKind::Script);
+        self.report_section("Stylesheets", |r| r.kind == ResourceKind::Stylesheet);
+        self.report_section("Media", |r| r.kind == ResourceKind::Media);
+        self.report_section("Favicons", |r| r.kind == ResourceKind::Favicon);
+        self.report_section("Frames", |r| r.kind == ResourceKind::Frame);
+
+        self.report_section("All resources", |_| true);
     }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("timestamp,local_time,internal,kind,url\n");
+        for result in &self.resources {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                result.timestamp.to_rfc3339(),
+                result.timestamp.with_timezone(&Local).to_rfc3339(),
+                result.internal,
+                result.kind.label(),
+                csv_field(&result.absolute),
+            ));
+        }
+        csv
+    }
+
+    pub fn to_json_feed(&self) -> Result<String> {
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1".to_string(),
+            title: self.title.clone().unwrap_or_else(|| self.url.clone()),
+            home_page_url: self.url.clone(),
+            items: self
+                .resources
+                .iter()
+                // `resources` is sorted oldest-first; a feed reads newest-first.
+                .rev()
+                .map(|result| JsonFeedItem {
+                    id: result.absolute.clone(),
+                    url: result.absolute.clone(),
+                    date_published: result.timestamp.to_rfc3339(),
+                    tags: vec![
+                        if result.internal {
+                            "internal"
+                        } else {
+                            "external"
+                        }
+                        .to_string(),
+                        result.kind.label().to_string(),
+                    ],
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&feed)?)
+    }
+}
+
+/// Quotes a CSV field, escaping any embedded double quotes.
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
 }
 
-pub async fn fetch_page(client: &Client, url: &str) -> Result<(HeaderMap, String)> {
+pub async fn fetch_page(url: &str, ctx: &FetchContext<'_>) -> Result<(HeaderMap, String)> {
     println!("{}", format!("Fetching page {}", url).dimmed());
-    let response = client.get(url).send().await?;
+
+    let cached = if ctx.refresh {
+        None
+    } else {
+        ctx.cache.and_then(|c| c.load(url))
+    };
+    let mut request = ctx.client.get(url);
+    if let Some(entry) = &cached {
+        request = apply_conditional_headers(request, entry);
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(CacheEntry {
+            body: Some(body), ..
+        }) = cached
+        {
+            let headers = response.headers().to_owned();
+            return Ok((headers, body));
+        }
+    }
+
     let headers = response.headers().to_owned();
     let html = response.text().await?;
+
+    if let Some(cache) = ctx.cache {
+        let entry = CacheEntry {
+            etag: header_str(&headers, "ETag"),
+            last_modified: header_str(&headers, "Last-Modified"),
+            timestamp: None,
+            body: Some(html.clone()),
+        };
+        let _ = cache.store(url, &entry);
+    }
+
     Ok((headers, html))
 }
 
+fn header_str(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+fn apply_conditional_headers(
+    request: reqwest::RequestBuilder,
+    entry: &CacheEntry,
+) -> reqwest::RequestBuilder {
+    let mut request = request;
+    if let Some(etag) = &entry.etag {
+        request = request.header("If-None-Match", etag);
+    }
+    if let Some(last_modified) = &entry.last_modified {
+        request = request.header("If-Modified-Since", last_modified);
+    }
+    request
+}
+
 fn extract_title(html: &str) -> Option<String> {
     let document = Html::parse_document(html);
     let title_selector = Selector::parse("title").unwrap();
@@ -149,66 +401,242 @@ fn extract_title(html: &str) -> Option<String> {
         .map(|el| el.inner_html())
 }
 
-async fn handle_image(
+/// Dating and authorship signals declared by the page itself, as opposed to
+/// the observed `Last-Modified` timestamps of its resources.
+#[derive(Debug, Clone, Default)]
+struct PageMetadata {
+    published: Option<DateTime<Utc>>,
+    modified: Option<DateTime<Utc>>,
+    author: Option<String>,
+}
+
+/// Reads `article:published_time` / `article:modified_time` / `author` meta
+/// tags plus any JSON-LD block, to surface a page's own claimed dating and
+/// authorship as a corroborating (or contradicting) signal alongside the
+/// observed resource timestamps.
+fn extract_metadata(html: &str) -> PageMetadata {
+    let document = Html::parse_document(html);
+    let mut metadata = PageMetadata {
+        published: meta_property(&document, "article:published_time")
+            .and_then(|v| parse_datetime(&v)),
+        modified: meta_property(&document, "article:modified_time")
+            .and_then(|v| parse_datetime(&v)),
+        author: meta_name(&document, "author").or_else(|| meta_property(&document, "og:author")),
+    };
+
+    for (published, modified, author) in extract_json_ld(&document) {
+        metadata.published = metadata.published.or(published);
+        metadata.modified = metadata.modified.or(modified);
+        metadata.author = metadata.author.or(author);
+    }
+
+    metadata
+}
+
+fn parse_datetime(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn meta_property(document: &Html, property: &str) -> Option<String> {
+    let selector = Selector::parse(&format!("meta[property=\"{}\"]", property)).ok()?;
+    document
+        .select(&selector)
+        .next()?
+        .value()
+        .attr("content")
+        .map(|v| v.to_string())
+}
+
+fn meta_name(document: &Html, name: &str) -> Option<String> {
+    let selector = Selector::parse(&format!("meta[name=\"{}\"]", name)).ok()?;
+    document
+        .select(&selector)
+        .next()?
+        .value()
+        .attr("content")
+        .map(|v| v.to_string())
+}
+
+type JsonLdSignals = (Option<DateTime<Utc>>, Option<DateTime<Utc>>, Option<String>);
+
+fn extract_json_ld(document: &Html) -> Vec<JsonLdSignals> {
+    let selector = Selector::parse(r#"script[type="application/ld+json"]"#).unwrap();
+    document
+        .select(&selector)
+        .filter_map(|el| serde_json::from_str::<serde_json::Value>(&el.inner_html()).ok())
+        .map(|value| {
+            let published = json_ld_datetime(&value, "datePublished");
+            let modified = json_ld_datetime(&value, "dateModified");
+            let author = json_ld_author(&value);
+            (published, modified, author)
+        })
+        .collect()
+}
+
+fn json_ld_datetime(value: &serde_json::Value, key: &str) -> Option<DateTime<Utc>> {
+    value.get(key)?.as_str().and_then(parse_datetime)
+}
+
+fn json_ld_author(value: &serde_json::Value) -> Option<String> {
+    match value.get("author")? {
+        serde_json::Value::String(name) => Some(name.clone()),
+        serde_json::Value::Object(author) => author.get("name")?.as_str().map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+async fn handle_resource(
     base_url: &str,
     address: &str,
-    client: &Client,
-    requested: &mut HashSet<String>,
+    kind: ResourceKind,
+    ctx: &FetchContext<'_>,
 ) -> Option<AnalysisResult> {
-    if address.is_empty() || requested.contains(address) {
-        return None;
-    }
-    requested.insert(address.to_string());
-    println!("{}", format!("Working on image {}", address).dimmed());
+    println!(
+        "{}",
+        format!("Working on {} {}", kind.label(), address).dimmed()
+    );
 
     let absolute = Url::parse(base_url).ok()?.join(address).ok()?;
-    let headers = client
-        .get(absolute.as_str())
-        .send()
-        .await
-        .ok()?
-        .headers()
-        .clone();
+    let internal = Url::parse(base_url).ok()?.host() == absolute.host();
+
+    let cached = if ctx.refresh {
+        None
+    } else {
+        ctx.cache.and_then(|c| c.load(absolute.as_str()))
+    };
+
+    let mut request = ctx.client.get(absolute.as_str());
+    if let Some(entry) = &cached {
+        request = apply_conditional_headers(request, entry);
+    }
+
+    let response = request.send().await.ok()?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(CacheEntry {
+            timestamp: Some(timestamp),
+            ..
+        }) = cached
+        {
+            return Some(AnalysisResult {
+                timestamp,
+                absolute: absolute.to_string(),
+                internal,
+                kind,
+            });
+        }
+    }
+
+    let headers = response.headers().clone();
     let last_modified = headers.get("Last-Modified").and_then(|h| h.to_str().ok())?;
     let timestamp = DateTime::parse_from_rfc2822(last_modified)
         .ok()?
         .with_timezone(&Utc);
 
-    let internal = Url::parse(base_url).ok()?.host() == absolute.host();
+    if let Some(cache) = ctx.cache {
+        let entry = CacheEntry {
+            etag: header_str(&headers, "ETag"),
+            last_modified: Some(last_modified.to_string()),
+            timestamp: Some(timestamp),
+            body: None,
+        };
+        let _ = cache.store(absolute.as_str(), &entry);
+    }
+
     Some(AnalysisResult {
         timestamp,
         absolute: absolute.to_string(),
         internal,
+        kind,
     })
 }
 
-pub async fn collect_images(html: &str, base_url: &str, client: &Client) -> Vec<AnalysisResult> {
+/// Walks the parsed document and gathers a deduplicated list of candidate
+/// resource addresses, tagged with the kind of resource they were found as.
+/// Dedup happens here, up front, so the fetching stage below can run every
+/// address concurrently without needing to share any state.
+fn collect_addresses(html: &str) -> Vec<(String, ResourceKind)> {
     let document = Html::parse_document(html);
     let mut requested = HashSet::new();
-    let mut images = Vec::new();
+    let mut addresses = Vec::new();
+    let mut push = |address: &str, kind: ResourceKind| {
+        if !address.is_empty()
+            && !address.starts_with("data:")
+            && requested.insert(address.to_string())
+        {
+            addresses.push((address.to_string(), kind));
+        }
+    };
 
-    // Collect images from <img> tags
     let img_selector = Selector::parse("img").unwrap();
     for element in document.select(&img_selector) {
         if let Some(src) = element.value().attr("src") {
-            if !src.starts_with("data:") {
-                if let Some(result) = handle_image(base_url, src, client, &mut requested).await {
-                    images.push(result);
-                }
-            }
+            push(src, ResourceKind::Image);
         }
     }
 
-    // Collect OpenGraph images
     let og_selector = Selector::parse("meta[property=\"og:image\"]").unwrap();
     for element in document.select(&og_selector) {
         if let Some(content) = element.value().attr("content") {
-            if let Some(result) = handle_image(base_url, content, client, &mut requested).await {
-                images.push(result);
-            }
+            push(content, ResourceKind::Image);
         }
     }
 
-    images.sort_by_key(|i| i.timestamp);
-    images
+    let script_selector = Selector::parse("script[src]").unwrap();
+    for element in document.select(&script_selector) {
+        if let Some(src) = element.value().attr("src") {
+            push(src, ResourceKind::Script);
+        }
+    }
+
+    let stylesheet_selector = Selector::parse("link[rel=\"stylesheet\"][href]").unwrap();
+    for element in document.select(&stylesheet_selector) {
+        if let Some(href) = element.value().attr("href") {
+            push(href, ResourceKind::Stylesheet);
+        }
+    }
+
+    // `rel~=` matches a whitespace-separated token, so "shortcut icon" (the
+    // common real-world form) matches alongside the bare "icon" rel.
+    let favicon_selector = Selector::parse("link[rel~=\"icon\"][href]").unwrap();
+    for element in document.select(&favicon_selector) {
+        if let Some(href) = element.value().attr("href") {
+            push(href, ResourceKind::Favicon);
+        }
+    }
+
+    let media_selector = Selector::parse("video[src], audio[src], source[src]").unwrap();
+    for element in document.select(&media_selector) {
+        if let Some(src) = element.value().attr("src") {
+            push(src, ResourceKind::Media);
+        }
+    }
+
+    let frame_selector = Selector::parse("iframe[src]").unwrap();
+    for element in document.select(&frame_selector) {
+        if let Some(src) = element.value().attr("src") {
+            push(src, ResourceKind::Frame);
+        }
+    }
+
+    addresses
+}
+
+pub async fn collect_resources(
+    html: &str,
+    base_url: &str,
+    ctx: &FetchContext<'_>,
+) -> Vec<AnalysisResult> {
+    let addresses = collect_addresses(html);
+
+    let mut resources: Vec<AnalysisResult> = stream::iter(addresses)
+        .map(|(address, kind)| async move { handle_resource(base_url, &address, kind, ctx).await })
+        .buffer_unordered(ctx.concurrency)
+        .filter_map(|result| async move { result })
+        .collect()
+        .await;
+
+    resources.sort_by_key(|r| r.timestamp);
+    resources
 }
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// A cached HTTP response for a single URL, used for conditional
+/// revalidation (`If-Modified-Since` / `If-None-Match`) so repeat runs
+/// against the same site don't re-download unchanged assets.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub body: Option<String>,
+}
+
+/// An on-disk cache, one JSON record per URL hash, rooted at `carbon14` under
+/// the platform cache directory (`~/.cache/carbon14` on Linux), or under the
+/// system temp directory when no cache directory is known.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Opens (creating if necessary) the default cache directory.
+    pub fn open() -> std::io::Result<Self> {
+        let dir = default_dir();
+        fs::create_dir_all(&dir)?;
+        Ok(Cache { dir })
+    }
+
+    fn path_for(&self, url: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    pub fn load(&self, url: &str) -> Option<CacheEntry> {
+        let contents = fs::read_to_string(self.path_for(url)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Writes a record to disk, holding an advisory exclusive lock on the
+    /// record file so concurrent fetches don't corrupt each other's writes.
+    ///
+    /// The lock is taken before the file is truncated: opening with
+    /// `truncate(true)` would clear the file's contents at `open()` time,
+    /// outside the lock, letting a second writer observe (or create) a
+    /// half-written record.
+    pub fn store(&self, url: &str, entry: &CacheEntry) -> std::io::Result<()> {
+        let path = self.path_for(url);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)?;
+        file.lock_exclusive()?;
+        file.set_len(0)?;
+        let json = serde_json::to_vec_pretty(entry).unwrap_or_default();
+        file.write_all(&json)?;
+        file.unlock()?;
+        Ok(())
+    }
+}
+
+fn default_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("carbon14")
+}
@@ -1,18 +1,74 @@
-use carbon_14::analysis::{fetch_page, Analysis};
+use carbon_14::analysis::{fetch_page, Analysis, FetchContext, DEFAULT_CONCURRENCY};
+use carbon_14::cache::Cache;
+use carbon_14::server;
 use chrono::Utc;
-use clap::{command, Parser};
+use clap::{command, Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::eyre;
 use color_eyre::Result;
 use reqwest::Client;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// URL of the page
-    url: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// URL of the page (omitted when using the `serve` subcommand)
+    url: Option<String>,
 
     /// Author to be included in the report
     #[arg(short, long)]
     author: Option<String>,
+
+    /// Maximum number of resource fetches to run concurrently (minimum 1)
+    #[arg(short = 'j', long, value_parser = parse_concurrency, default_value_t = DEFAULT_CONCURRENCY)]
+    concurrency: usize,
+
+    /// Output format for the report
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Disable the on-disk response cache entirely
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Bypass cached entries and force unconditional fetches, refreshing the cache
+    #[arg(long)]
+    refresh: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start an HTTP server exposing Carbon14 as a service
+    Serve {
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to bind. Defaults to loopback-only; the server fetches
+        /// whatever URL a caller supplies, so widen this only behind auth
+        /// or on a trusted network.
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+    },
+}
+
+/// Validates the `--concurrency` flag, rejecting 0 (clap's `.range()` builder
+/// only supports the fixed-width integer parsers, not `usize`).
+fn parse_concurrency(s: &str) -> Result<usize, String> {
+    let value: usize = s.parse().map_err(|_| format!("`{s}` isn't a number"))?;
+    if value < 1 {
+        return Err("concurrency must be at least 1".to_string());
+    }
+    Ok(value)
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum Format {
+    Text,
+    Json,
+    Csv,
+    Jsonfeed,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -20,14 +76,37 @@ async fn main() -> Result<()> {
     color_eyre::install()?;
 
     let args = Args::parse();
+
+    if let Some(Command::Serve { port, bind }) = args.command {
+        return server::serve(&bind, port).await;
+    }
+
+    let url = args
+        .url
+        .ok_or_else(|| eyre!("a URL is required unless using the `serve` subcommand"))?;
+
     let client = Client::new();
     let start = Utc::now();
 
-    let (headers, html) = fetch_page(&client, &args.url).await?;
+    let cache = if args.no_cache {
+        None
+    } else {
+        Cache::open().ok()
+    };
+    let ctx = FetchContext::new(&client, args.concurrency, cache.as_ref(), args.refresh);
+
+    let (headers, html) = fetch_page(&url, &ctx).await?;
     let end = Utc::now();
+    let format = args.format.clone();
+
+    let analysis = Analysis::new(url, args.author, &html, headers, start, end, &ctx).await;
 
-    let analysis = Analysis::new(args.url, args.author, &html, headers, start, end, &client).await;
-    analysis.report();
+    match format {
+        Format::Text => analysis.report(),
+        Format::Json => println!("{}", analysis.to_json()?),
+        Format::Csv => print!("{}", analysis.to_csv()),
+        Format::Jsonfeed => println!("{}", analysis.to_json_feed()?),
+    }
 
     Ok(())
 }
@@ -0,0 +1,80 @@
+use crate::analysis::{fetch_page, Analysis, FetchContext, DEFAULT_CONCURRENCY};
+use crate::cache::Cache;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use color_eyre::Result;
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Shared state handed to every request: a single `reqwest::Client` and the
+/// on-disk response cache, both reused across queries the same way the CLI
+/// reuses them within a single run.
+#[derive(Clone)]
+struct AppState {
+    client: Client,
+    cache: Option<Arc<Cache>>,
+}
+
+#[derive(Deserialize)]
+struct AnalyzeQuery {
+    url: String,
+    author: Option<String>,
+    format: Option<String>,
+}
+
+/// Starts the `serve` daemon, exposing `GET /analyze?url=...&author=...` as a
+/// long-running alternative to invoking the CLI once per page.
+///
+/// The server fetches whatever `url` a caller supplies, so it binds to
+/// `bind` (loopback by default, see `--bind`) rather than all interfaces;
+/// widen that only behind auth or on a trusted network.
+pub async fn serve(bind: &str, port: u16) -> Result<()> {
+    let cache = Cache::open().ok().map(Arc::new);
+    let state = AppState {
+        client: Client::new(),
+        cache,
+    };
+
+    let app = Router::new()
+        .route("/analyze", get(analyze))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind((bind, port)).await?;
+    println!("Listening on http://{}:{}", bind, port);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+async fn analyze(
+    State(state): State<AppState>,
+    Query(query): Query<AnalyzeQuery>,
+) -> impl IntoResponse {
+    match run_analysis(&state, query).await {
+        Ok(body) => (StatusCode::OK, body),
+        Err(err) => (
+            StatusCode::BAD_GATEWAY,
+            format!("error analyzing page: {err}"),
+        ),
+    }
+}
+
+async fn run_analysis(state: &AppState, query: AnalyzeQuery) -> Result<String> {
+    let start = Utc::now();
+    let cache = state.cache.as_deref();
+    let ctx = FetchContext::new(&state.client, DEFAULT_CONCURRENCY, cache, false);
+    let (headers, html) = fetch_page(&query.url, &ctx).await?;
+    let end = Utc::now();
+
+    let analysis = Analysis::new(query.url, query.author, &html, headers, start, end, &ctx).await;
+
+    match query.format.as_deref() {
+        Some("jsonfeed") => analysis.to_json_feed(),
+        _ => analysis.to_json(),
+    }
+}